@@ -1,26 +1,44 @@
 extern crate getopts;
+extern crate regex;
 
 use getopts::Options;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashSet;
+use std::env;
 use std::error::Error;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
 
 pub struct Config {
     pub query: String,
     pub filename: String,
     pub case_sensitive: bool,
+    pub fixed_strings: bool,
+    pub line_numbers: bool,
+    pub count: bool,
+    pub invert_match: bool,
+    pub before_context: usize,
+    pub after_context: usize,
 }
 
 pub fn parse_config(args: &[String]) -> Result<Config, &'static str> {
-    if args.len() < 3 {
+    if args.len() < 2 {
         return Err("not enough arguments");
     }
 
     let mut opts = Options::new();
     opts.optflag("i", "insensitive", "set insensitive mode");
+    opts.optflag("F", "fixed-strings", "treat QUERY as a literal string");
+    opts.optflag("n", "line-numbers", "print the line number before each match");
+    opts.optflag("c", "count", "print only a count of matching lines");
+    opts.optflag("v", "invert-match", "select non-matching lines");
+    opts.optopt("A", "after-context", "print N lines of trailing context", "N");
+    opts.optopt("B", "before-context", "print N lines of leading context", "N");
+    opts.optopt("C", "context", "print N lines of leading and trailing context", "N");
     opts.optflag("h", "help", "print this help menu");
 
-    let matches = opts.parse(&args[1..]).unwrap();
+    let matches = opts.parse(&args[1..]).map_err(|_| "invalid arguments")?;
 
     let program_name = args[0].clone();
     if matches.opt_present("h") {
@@ -28,72 +46,197 @@ pub fn parse_config(args: &[String]) -> Result<Config, &'static str> {
         return Err("it's not error. displayed help page.");
     }
 
-    let filename = args[args.len() - 1].clone();
-    if filename.starts_with("-") {
-        return Err("arguments should be [options] QUERY FILENAME");
+    if matches.free.is_empty() {
+        return Err("not enough arguments");
     }
 
-    let query = args[args.len() - 2].clone();
-    if query.starts_with("-") {
-        return Err("arguments should be [options] QUERY FILENAME");
-    }
+    let query = matches.free[0].clone();
 
-    let case_sensitive = if matches.opt_present("i") {
-        false
-    } else {
-        true
+    // FILENAME is optional: "-" or an absent second positional both mean stdin.
+    let filename = matches
+        .free
+        .get(1)
+        .cloned()
+        .unwrap_or_else(|| String::from("-"));
+
+    let case_sensitive = resolve_case_sensitive(matches.opt_present("i"), env::var("CASE_INSENSITIVE").ok());
+
+    let fixed_strings = matches.opt_present("F");
+    let line_numbers = matches.opt_present("n");
+    let count = matches.opt_present("c");
+    let invert_match = matches.opt_present("v");
+
+    let parse_context = |name: &str| -> Result<Option<usize>, &'static str> {
+        match matches.opt_str(name) {
+            Some(n) => n.parse::<usize>().map(Some).map_err(|_| "invalid context value"),
+            None => Ok(None),
+        }
     };
 
+    let context = parse_context("C")?;
+    let before_context = parse_context("B")?.or(context).unwrap_or(0);
+    let after_context = parse_context("A")?.or(context).unwrap_or(0);
+
     Ok(Config {
         query,
         filename,
         case_sensitive,
+        fixed_strings,
+        line_numbers,
+        count,
+        invert_match,
+        before_context,
+        after_context,
     })
 }
 
+// The -i flag takes precedence; otherwise CASE_INSENSITIVE (set to any
+// value) flips the search to case-insensitive. Takes the env lookup as a
+// plain value so tests can exercise both directions without touching the
+// process-wide environment.
+fn resolve_case_sensitive(insensitive_flag: bool, case_insensitive_env: Option<String>) -> bool {
+    if insensitive_flag {
+        false
+    } else {
+        case_insensitive_env.is_none()
+    }
+}
+
 fn print_usage(program_name: &str, opts: Options) {
-    let brief = format!("Usage: {} [options] QUERY FILENAME", program_name);
+    let brief = format!("Usage: {} [options] QUERY [FILENAME]", program_name);
     print!("{}", opts.usage(&brief));
 }
 
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let mut f = File::open(config.filename)?;
+    let mut reader: Box<dyn Read> = if config.filename == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(&config.filename)?)
+    };
 
     let mut contents = String::new();
-    f.read_to_string(&mut contents)?;
+    reader.read_to_string(&mut contents)?;
 
-    let results = if config.case_sensitive {
-        search(&config.query, &contents)
+    let mut results = if config.fixed_strings {
+        if config.case_sensitive {
+            search(&config.query, &contents)
+        } else {
+            search_case_insensitive(&config.query, &contents)
+        }
     } else {
-        search_case_insensitive(&config.query, &contents)
+        let pattern = RegexBuilder::new(&config.query)
+            .case_insensitive(!config.case_sensitive)
+            .build()?;
+        search_regex(&pattern, &contents)
     };
 
-    for line in results {
-        println!("{}", line);
+    if config.invert_match {
+        results = invert_matches(&results, &contents);
+    }
+
+    if config.count {
+        println!("{}", results.len());
+        return Ok(());
+    }
+
+    if config.before_context == 0 && config.after_context == 0 {
+        for (line_number, line) in results {
+            print_match(line_number, line, config.line_numbers);
+        }
+        return Ok(());
+    }
+
+    let lines: Vec<&str> = contents.lines().collect();
+    let hits: Vec<usize> = results.iter().map(|&(i, _)| i).collect();
+    let groups = context_windows(&hits, config.before_context, config.after_context, lines.len());
+
+    for (group_index, &(start, end)) in groups.iter().enumerate() {
+        if group_index > 0 {
+            println!("--");
+        }
+        for i in start..=end {
+            print_match(i, lines[i - 1], config.line_numbers);
+        }
     }
 
     Ok(())
 }
 
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+fn print_match(line_number: usize, line: &str, with_line_numbers: bool) {
+    if with_line_numbers {
+        println!("{}:{}", line_number, line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// Returns every line of `contents` whose 1-based index is NOT present in `matches`.
+fn invert_matches<'a>(matches: &[(usize, &'a str)], contents: &'a str) -> Vec<(usize, &'a str)> {
+    let matched: HashSet<usize> = matches.iter().map(|&(i, _)| i).collect();
+
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(i, _)| !matched.contains(i))
+        .collect()
+}
+
+/// Builds the `[start, end]` (1-based, inclusive) line ranges to print around each hit,
+/// clamped to `[1, line_count]` and merged where ranges touch or overlap.
+fn context_windows(hits: &[usize], before: usize, after: usize, line_count: usize) -> Vec<(usize, usize)> {
+    let mut windows: Vec<(usize, usize)> = hits
+        .iter()
+        .map(|&i| {
+            let start = i.saturating_sub(before).max(1);
+            let end = (i + after).min(line_count);
+            (start, end)
+        })
+        .collect();
+    windows.sort();
+
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in windows {
+        match groups.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => groups.push((start, end)),
+        }
+    }
+
+    groups
+}
+
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<(usize, &'a str)> {
     let mut results = Vec::new();
 
-    for line in contents.lines() {
+    for (i, line) in contents.lines().enumerate() {
         if line.contains(query) {
-            results.push(line);
+            results.push((i + 1, line));
         }
     }
 
     results
 }
 
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<(usize, &'a str)> {
     let query = query.to_lowercase();
     let mut results = Vec::new();
 
-    for line in contents.lines() {
+    for (i, line) in contents.lines().enumerate() {
         if line.to_lowercase().contains(&query) {
-            results.push(line);
+            results.push((i + 1, line));
+        }
+    }
+
+    results
+}
+
+pub fn search_regex<'a>(pattern: &Regex, contents: &'a str) -> Vec<(usize, &'a str)> {
+    let mut results = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        if pattern.is_match(line) {
+            results.push((i + 1, line));
         }
     }
 
@@ -137,29 +280,29 @@ mod tests_for_parse_config {
     }
 
     #[test]
-    fn parse_config_abnormal_test_no_query() {
-        let command_input = "minigrep poem.txt";
+    fn parse_config_normal_test_no_filename_defaults_to_stdin() {
+        let command_input = "minigrep to";
         let args: Vec<String> = command_input
             .split_whitespace()
             .map(|s| s.to_string())
             .collect();
 
-        let res = parse_config(&args).err().unwrap();
-        let expect = "not enough arguments";
-        assert_eq!(expect, res);
+        let res = parse_config(&args).unwrap();
+        assert_eq!("to", res.query);
+        assert_eq!("-", res.filename);
     }
 
     #[test]
-    fn parse_config_abnormal_test_no_filename() {
-        let command_input = "minigrep to";
+    fn parse_config_normal_test_dash_filename_means_stdin() {
+        let command_input = "minigrep to -";
         let args: Vec<String> = command_input
             .split_whitespace()
             .map(|s| s.to_string())
             .collect();
 
-        let res = parse_config(&args).err().unwrap();
-        let expect = "not enough arguments";
-        assert_eq!(expect, res);
+        let res = parse_config(&args).unwrap();
+        assert_eq!("to", res.query);
+        assert_eq!("-", res.filename);
     }
 
     #[test]
@@ -176,30 +319,73 @@ mod tests_for_parse_config {
     }
 
     #[test]
-    fn parse_config_abnormal_test_param_error_1() {
+    fn parse_config_normal_test_query_only_with_flag() {
         let command_input = "minigrep -i poem.txt";
         let args: Vec<String> = command_input
             .split_whitespace()
             .map(|s| s.to_string())
             .collect();
 
-        let res = parse_config(&args).err().unwrap();
-        let expect = "arguments shoudl be [options] QUERY FILENAME";
-        assert_eq!(expect, res);
+        let res = parse_config(&args).unwrap();
+        assert_eq!("poem.txt", res.query);
+        assert_eq!("-", res.filename);
+        assert_eq!("false", res.case_sensitive.to_string());
     }
 
     #[test]
-    fn parse_config_abnormal_test_param_error_2() {
-        let command_input = "minigrep poem.txt -i";
+    fn parse_config_normal_test_line_numbers_and_count() {
+        let command_input = "minigrep -n -c to poem.txt";
+        let args: Vec<String> = command_input
+            .split_whitespace()
+            .map(|s| s.to_string())
+            .collect();
+
+        let res = parse_config(&args).unwrap();
+        assert!(res.line_numbers);
+        assert!(res.count);
+    }
+
+    #[test]
+    fn parse_config_abnormal_test_missing_option_argument() {
+        let command_input = "minigrep to poem.txt -A";
         let args: Vec<String> = command_input
             .split_whitespace()
             .map(|s| s.to_string())
             .collect();
 
         let res = parse_config(&args).err().unwrap();
-        let expect = "arguments shoudl be [options] QUERY FILENAME";
+        let expect = "invalid arguments";
         assert_eq!(expect, res);
     }
+
+}
+
+/* ----------------------------------------------------------------
+    tests for resolve_case_sensitive
+---------------------------------------------------------------- */
+#[cfg(test)]
+mod tests_for_resolve_case_sensitive {
+    use super::*;
+
+    #[test]
+    fn defaults_to_case_sensitive() {
+        assert!(resolve_case_sensitive(false, None));
+    }
+
+    #[test]
+    fn env_var_flips_to_case_insensitive() {
+        assert!(!resolve_case_sensitive(false, Some(String::from("1"))));
+    }
+
+    #[test]
+    fn flag_alone_flips_to_case_insensitive() {
+        assert!(!resolve_case_sensitive(true, None));
+    }
+
+    #[test]
+    fn flag_and_env_var_agree() {
+        assert!(!resolve_case_sensitive(true, Some(String::from("1"))));
+    }
 }
 
 /* ----------------------------------------------------------------
@@ -218,7 +404,7 @@ safe, fast, productive.
 Pick three.
 Duct tape";
 
-        assert_eq!(vec!["safe, fast, productive."], search(query, contents));
+        assert_eq!(vec![(2, "safe, fast, productive.")], search(query, contents));
     }
 
     #[test]
@@ -230,8 +416,104 @@ safe, fast, productive.
 Trust me.";
 
         assert_eq!(
-            vec!["Rust:", "Trust me."],
+            vec![(1, "Rust:"), (3, "Trust me.")],
             search_case_insensitive(query, contents)
         );
     }
 }
+
+/* ----------------------------------------------------------------
+    tests for search_regex
+---------------------------------------------------------------- */
+#[cfg(test)]
+mod tests_for_search_regex {
+    use super::*;
+
+    #[test]
+    fn matches_pattern() {
+        let pattern = RegexBuilder::new(r"d\w+t").build().unwrap();
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape";
+
+        assert_eq!(
+            vec![(2, "safe, fast, productive.")],
+            search_regex(&pattern, contents)
+        );
+    }
+
+    #[test]
+    fn matches_pattern_case_insensitive() {
+        let pattern = RegexBuilder::new(r"d\w+t")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape";
+
+        assert_eq!(
+            vec![(2, "safe, fast, productive."), (4, "Duct tape")],
+            search_regex(&pattern, contents)
+        );
+    }
+
+    #[test]
+    #[allow(clippy::invalid_regex)]
+    fn invalid_pattern_is_an_error() {
+        let result = RegexBuilder::new(r"(unclosed").build();
+        assert!(result.is_err());
+    }
+}
+
+/* ----------------------------------------------------------------
+    tests for invert_matches
+---------------------------------------------------------------- */
+#[cfg(test)]
+mod tests_for_invert_matches {
+    use super::*;
+
+    #[test]
+    fn returns_the_non_matching_lines() {
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape";
+        let matches = search("duct", contents);
+
+        assert_eq!(
+            vec![(1, "Rust:"), (3, "Pick three."), (4, "Duct tape")],
+            invert_matches(&matches, contents)
+        );
+    }
+}
+
+/* ----------------------------------------------------------------
+    tests for context_windows
+---------------------------------------------------------------- */
+#[cfg(test)]
+mod tests_for_context_windows {
+    use super::*;
+
+    #[test]
+    fn overlapping_windows_merge_into_one_group() {
+        // hits at 3 and 5 with before=1/after=1 give [2,4] and [4,6], which touch.
+        assert_eq!(vec![(2, 6)], context_windows(&[3, 5], 1, 1, 10));
+    }
+
+    #[test]
+    fn distant_windows_stay_separate_groups() {
+        // hits at 2 and 9 with before=1/after=1 give [1,3] and [8,10], which don't touch.
+        assert_eq!(vec![(1, 3), (8, 10)], context_windows(&[2, 9], 1, 1, 10));
+    }
+
+    #[test]
+    fn windows_clamp_to_line_bounds() {
+        assert_eq!(vec![(1, 3), (8, 10)], context_windows(&[1, 10], 2, 2, 10));
+    }
+}